@@ -0,0 +1,157 @@
+//! Support for restricting coverage collection to files that differ from a
+//! git reference (the `changed` / `diff_base` config options).
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Error returned when the changed-file set can't be determined, typically
+/// because the working directory isn't a git repository.
+#[derive(Debug)]
+pub struct ChangedFilesError(String);
+
+impl fmt::Display for ChangedFilesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for ChangedFilesError {}
+
+/// Returns the set of paths, relative to `base_dir`, that differ from
+/// `diff_base`, combining files modified in tracked history with untracked
+/// files. Renamed files are reported under their new path, matching git's
+/// own `--name-only` output.
+///
+/// `git diff --name-only` always reports paths relative to the repo root,
+/// not to `current_dir`, so when `base_dir` is a workspace subdirectory
+/// those paths are rebased onto `base_dir` using the repo-root-to-base_dir
+/// prefix; `git ls-files --others` already reports paths relative to
+/// `current_dir` and needs no adjustment.
+pub fn changed_files(
+    base_dir: &Path,
+    diff_base: &str,
+) -> Result<HashSet<PathBuf>, ChangedFilesError> {
+    let prefix = repo_root_to_base_dir_prefix(base_dir)?;
+
+    let mut files = HashSet::new();
+    for path in run_git(
+        base_dir,
+        &["diff", "--name-only", "--diff-filter=ACMRT", diff_base],
+    )? {
+        if let Some(relative) = strip_prefix(&path, &prefix) {
+            files.insert(relative);
+        }
+    }
+    files.extend(run_git(
+        base_dir,
+        &["ls-files", "--others", "--exclude-standard"],
+    )?);
+    Ok(files)
+}
+
+/// Returns the path from the git repo root down to `base_dir`, so
+/// repo-root-relative paths (as `git diff --name-only` reports them) can be
+/// rebased onto `base_dir`. Empty when `base_dir` is the repo root itself.
+fn repo_root_to_base_dir_prefix(base_dir: &Path) -> Result<PathBuf, ChangedFilesError> {
+    let output = Command::new("git")
+        .args(&["rev-parse", "--show-toplevel"])
+        .current_dir(base_dir)
+        .output()
+        .map_err(|e| {
+            ChangedFilesError(format!("failed to run `git rev-parse --show-toplevel`: {}", e))
+        })?;
+    if !output.status.success() {
+        return Err(ChangedFilesError(format!(
+            "`git rev-parse --show-toplevel` failed in {}, is this a git repository? {}",
+            base_dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let repo_root = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim());
+    let repo_root = fs::canonicalize(&repo_root).unwrap_or(repo_root);
+    let base_dir = fs::canonicalize(base_dir).unwrap_or_else(|_| base_dir.to_path_buf());
+    Ok(super::path_relative_from(&base_dir, &repo_root).unwrap_or_default())
+}
+
+/// Strips `prefix` from a repo-root-relative path, discarding paths that
+/// fall outside `base_dir` entirely (they're not part of this project's
+/// coverage). An empty `prefix` means `base_dir` is the repo root, so the
+/// path is already base_dir-relative.
+fn strip_prefix(path: &Path, prefix: &Path) -> Option<PathBuf> {
+    if prefix.as_os_str().is_empty() {
+        Some(path.to_path_buf())
+    } else {
+        path.strip_prefix(prefix).ok().map(|p| p.to_path_buf())
+    }
+}
+
+fn run_git(base_dir: &Path, args: &[&str]) -> Result<Vec<PathBuf>, ChangedFilesError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(base_dir)
+        .output()
+        .map_err(|e| ChangedFilesError(format!("failed to run `git {}`: {}", args.join(" "), e)))?;
+
+    if !output.status.success() {
+        return Err(ChangedFilesError(format!(
+            "`git {}` failed in {}, is this a git repository? {}",
+            args.join(" "),
+            base_dir.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(PathBuf::from)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errors_when_not_a_git_repo() {
+        let dir = std::env::temp_dir().join("tarpaulin-changed-files-not-a-repo");
+        let _ = fs::create_dir_all(&dir);
+        assert!(changed_files(&dir, "HEAD").is_err());
+    }
+
+    #[test]
+    fn changed_files_are_relative_to_a_subdirectory_base_dir() {
+        let root = std::env::temp_dir().join(format!(
+            "tarpaulin-changed-e2e-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let sub = root.join("crate");
+        fs::create_dir_all(&sub).unwrap();
+
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .args(args)
+                .current_dir(&root)
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        git(&["config", "user.email", "test@example.com"]);
+        git(&["config", "user.name", "test"]);
+        fs::write(sub.join("lib.rs"), "fn a() {}\n").unwrap();
+        git(&["add", "."]);
+        git(&["commit", "-q", "-m", "init"]);
+
+        fs::write(sub.join("lib.rs"), "fn a() {}\nfn b() {}\n").unwrap();
+
+        let files = changed_files(&sub, "HEAD").unwrap();
+        assert!(files.contains(&PathBuf::from("lib.rs")));
+
+        fs::remove_dir_all(&root).ok();
+    }
+}
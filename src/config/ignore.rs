@@ -0,0 +1,214 @@
+//! Support for gitignore-style ignore files (`.gitignore` and
+//! `.tarpaulinignore`) used to exclude paths from coverage collection.
+
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A single compiled ignore pattern translated from gitignore syntax.
+#[derive(Debug)]
+struct IgnorePattern {
+    regex: Regex,
+    negate: bool,
+    dir_only: bool,
+}
+
+/// Matches paths against a set of gitignore-style patterns collected from
+/// `.tarpaulinignore` and `.gitignore` files found walking up from the base
+/// directory. Patterns are applied in the order they were loaded with
+/// last-match-wins semantics, mirroring how git itself resolves overlapping
+/// and negated (`!`) rules.
+#[derive(Debug, Default)]
+pub struct GitignoreMatcher {
+    patterns: Vec<IgnorePattern>,
+}
+
+impl GitignoreMatcher {
+    /// Builds a matcher from `.gitignore` and `.tarpaulinignore` files
+    /// found in `base_dir` and each of its ancestors; with `no_ignore` set,
+    /// neither is loaded and the matcher excludes nothing. Files closer to the filesystem root are loaded first so
+    /// patterns nearer to `base_dir` take precedence, matching git's own
+    /// resolution order. Anchored (`/`-prefixed) patterns are anchored
+    /// relative to `base_dir`, not to the filesystem root, since that's
+    /// the space `is_excluded` matches paths in.
+    pub fn discover(base_dir: &Path, no_ignore: bool) -> Self {
+        let mut matcher = GitignoreMatcher::default();
+        if no_ignore {
+            return matcher;
+        }
+        let mut dirs = vec![base_dir.to_path_buf()];
+        let mut cur = base_dir;
+        while let Some(parent) = cur.parent() {
+            dirs.push(parent.to_path_buf());
+            cur = parent;
+        }
+        for dir in dirs.into_iter().rev() {
+            // Path from this ignore file's directory down to base_dir, so
+            // an anchored pattern in an ancestor's .gitignore still lines
+            // up with the base_dir-relative paths `is_excluded` is called
+            // with. Empty for base_dir's own ignore files.
+            let anchor = super::path_relative_from(base_dir, &dir).unwrap_or_default();
+            matcher.load_file(&dir.join(".gitignore"), &anchor);
+            matcher.load_file(&dir.join(".tarpaulinignore"), &anchor);
+        }
+        matcher
+    }
+
+    fn load_file(&mut self, path: &Path, anchor: &Path) {
+        if let Ok(contents) = fs::read_to_string(path) {
+            for line in contents.lines() {
+                if let Some(pattern) = IgnorePattern::parse(line, anchor) {
+                    self.patterns.push(pattern);
+                }
+            }
+        }
+    }
+
+    /// Returns whether `path` (relative to the project base dir) is
+    /// excluded by the loaded patterns.
+    pub fn is_excluded(&self, path: &Path) -> bool {
+        let path_str = path.to_string_lossy().replace('\\', "/");
+        let mut excluded = false;
+        for pattern in &self.patterns {
+            if pattern.regex.is_match(&path_str) {
+                excluded = !pattern.negate;
+            }
+        }
+        excluded
+    }
+}
+
+impl IgnorePattern {
+    /// Parses a single line of a `.gitignore`/`.tarpaulinignore` file,
+    /// anchoring `/`-prefixed patterns to `anchor` (the ignore file's own
+    /// directory). Returns `None` for blank lines and comments.
+    fn parse(line: &str, anchor: &Path) -> Option<Self> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let negate = line.starts_with('!');
+        let mut pat = if negate { &line[1..] } else { line };
+        let dir_only = pat.ends_with('/');
+        if dir_only {
+            pat = &pat[..pat.len() - 1];
+        }
+        let anchored = pat.starts_with('/');
+        let pat = pat.trim_start_matches('/');
+        let regex = Self::translate(pat, anchored, dir_only, anchor)?;
+        Some(IgnorePattern {
+            regex,
+            negate,
+            dir_only,
+        })
+    }
+
+    /// Translates a single gitignore glob into a regex matching
+    /// forward-slash-separated paths relative to the project base dir.
+    /// `**` matches any number of intermediate directories, a leading `/`
+    /// anchors the pattern to `anchor` (itself expressed relative to the
+    /// project base dir, empty for the base dir's own ignore file), and an
+    /// unanchored pattern matches at any path segment, per gitignore
+    /// semantics. A directory-only (trailing `/`) pattern only matches
+    /// paths nested underneath it, not a file sharing its exact name.
+    fn translate(pat: &str, anchored: bool, dir_only: bool, anchor: &Path) -> Option<Regex> {
+        let mut re = String::new();
+        if anchored {
+            re.push('^');
+            let prefix = anchor.to_string_lossy().replace('\\', "/");
+            if !prefix.is_empty() {
+                re.push_str(&regex::escape(&prefix));
+                re.push('/');
+            }
+        } else {
+            re.push_str("(^|.*/)");
+        }
+
+        let mut chars = pat.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => {
+                    if chars.peek() == Some(&'*') {
+                        chars.next();
+                        if chars.peek() == Some(&'/') {
+                            chars.next();
+                            re.push_str("(.*/)?");
+                        } else {
+                            re.push_str(".*");
+                        }
+                    } else {
+                        re.push_str("[^/]*");
+                    }
+                }
+                '?' => re.push_str("[^/]"),
+                '.' => re.push_str("\\."),
+                other => re.push(other),
+            }
+        }
+        if dir_only {
+            re.push_str("/.+$");
+        } else {
+            re.push_str("(/.*)?$");
+        }
+        Regex::new(&re).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchored_pattern_matches_only_from_root() {
+        let pattern = IgnorePattern::parse("/target", Path::new("")).unwrap();
+        assert!(pattern.regex.is_match("target"));
+        assert!(pattern.regex.is_match("target/debug/build.rs"));
+        assert!(!pattern.regex.is_match("src/target"));
+    }
+
+    #[test]
+    fn unanchored_pattern_matches_any_segment() {
+        let pattern = IgnorePattern::parse("*.generated.rs", Path::new("")).unwrap();
+        assert!(pattern.regex.is_match("foo.generated.rs"));
+        assert!(pattern.regex.is_match("src/foo.generated.rs"));
+        assert!(!pattern.regex.is_match("src/foo.rs"));
+    }
+
+    #[test]
+    fn double_star_matches_intermediate_dirs() {
+        let pattern = IgnorePattern::parse("/src/**/generated", Path::new("")).unwrap();
+        assert!(pattern.regex.is_match("src/generated"));
+        assert!(pattern.regex.is_match("src/a/b/generated"));
+        assert!(!pattern.regex.is_match("other/generated"));
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_nested_paths() {
+        let pattern = IgnorePattern::parse("/build/", Path::new("")).unwrap();
+        assert!(pattern.regex.is_match("build/debug/build.rs"));
+        assert!(!pattern.regex.is_match("build"));
+        assert!(!pattern.regex.is_match("src/build"));
+    }
+
+    #[test]
+    fn anchor_relative_to_base_dir_matches_base_dir_paths() {
+        // Simulates an ignore file found in an ancestor of base_dir: the
+        // anchor is the path from that ancestor down to base_dir.
+        let pattern = IgnorePattern::parse("/target", Path::new("crate")).unwrap();
+        assert!(pattern.regex.is_match("crate/target/debug/build.rs"));
+        assert!(!pattern.regex.is_match("target/debug/build.rs"));
+    }
+
+    #[test]
+    fn negation_overrides_later_in_last_match_wins_order() {
+        let mut matcher = GitignoreMatcher::default();
+        matcher
+            .patterns
+            .push(IgnorePattern::parse("*.rs", Path::new("")).unwrap());
+        matcher
+            .patterns
+            .push(IgnorePattern::parse("!keep.rs", Path::new("")).unwrap());
+        assert!(matcher.is_excluded(Path::new("drop.rs")));
+        assert!(!matcher.is_excluded(Path::new("keep.rs")));
+    }
+}
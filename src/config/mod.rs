@@ -7,15 +7,22 @@ use log::{error, info};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::{Error, ErrorKind, Read};
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
+mod changed;
+mod ignore;
 mod parse;
 pub mod types;
+mod watch;
+
+use self::changed::changed_files;
+use self::ignore::GitignoreMatcher;
+pub use self::watch::{watch, CoverageSummary, Debouncer};
 
 /// Specifies the current configuration tarpaulin is using.
 #[derive(Debug, Deserialize, Serialize)]
@@ -26,6 +33,12 @@ pub struct Config {
     pub manifest: PathBuf,
     /// Path to a tarpaulin.toml config file
     pub config: Option<PathBuf>,
+    /// Name of another table in the same config file this profile inherits
+    /// from. Scalar fields the table leaves unset and list fields left
+    /// empty fall back to the parent's values; list fields whose entries are all
+    /// prefixed with `+` are appended to the parent's list instead of
+    /// replacing it.
+    pub inherits: Option<String>,
     /// Path to the projects cargo manifest
     pub root: Option<String>,
     /// Flag to also run tests with the ignored attribute
@@ -79,8 +92,22 @@ pub struct Config {
     pub target_dir: Option<PathBuf>,
     /// Run tarpaulin on project without accessing the network
     pub offline: bool,
+    /// Requests watch mode, as provided by the `watch` function (rerun
+    /// coverage whenever a relevant `.rs` file or `Cargo.toml` changes).
+    /// The flag is only stored: nothing in this module reads it or calls
+    /// `watch` when it is set.
+    pub watch: bool,
+    /// Quiet period to wait for a burst of filesystem events to settle
+    /// before triggering a watch-mode rerun.
+    pub watch_debounce: Duration,
     /// Types of tests for tarpaulin to collect coverage on
     pub run_types: Vec<RunType>,
+    /// Nextest profile name (the `cargo nextest run --profile` value),
+    /// recorded for when `run_types` includes `RunType::Nextest`.
+    pub nextest_profile: Option<String>,
+    /// Partition/shard spec (e.g. `count:1/4`, the
+    /// `cargo nextest run --partition` value), recorded for nextest runs.
+    pub nextest_partition: Option<String>,
     /// Packages to include when building the target project
     pub packages: Vec<String>,
     /// Packages to exclude from testing
@@ -90,6 +117,24 @@ pub struct Config {
     excluded_files: RefCell<Vec<Regex>>,
     /// Files to exclude from testing in uncompiled form (for serde)
     excluded_files_raw: Vec<String>,
+    /// Compiled `.gitignore`/`.tarpaulinignore` matcher, lazily discovered
+    /// from `get_base_dir()` the first time `exclude_path` is called.
+    #[serde(skip_deserializing, skip_serializing)]
+    ignore_matcher: RefCell<Option<GitignoreMatcher>>,
+    /// Disables loading of `.gitignore` and `.tarpaulinignore` files,
+    /// restoring the old explicit-exclude-only behavior.
+    pub no_ignore: bool,
+    /// Restrict coverage collection and reporting to files changed relative
+    /// to `diff_base`.
+    pub changed: bool,
+    /// Git reference to diff against when `changed` is set. Defaults to
+    /// `HEAD`.
+    pub diff_base: Option<String>,
+    /// Cache of paths (relative to the base dir) changed relative to
+    /// `diff_base`, lazily populated the first time `exclude_path` is
+    /// called with `changed` set.
+    #[serde(skip_deserializing, skip_serializing)]
+    changed_files_cache: RefCell<Option<HashSet<PathBuf>>>,
     /// Varargs to be forwarded to the test executables.
     pub varargs: Vec<String>,
     /// Features to include in the target project build
@@ -105,8 +150,11 @@ impl Default for Config {
         Config {
             name: String::new(),
             run_types: vec![RunType::Tests],
+            nextest_profile: None,
+            nextest_partition: None,
             manifest: default_manifest(),
             config: None,
+            inherits: None,
             root: Default::default(),
             run_ignored: false,
             ignore_tests: false,
@@ -131,6 +179,11 @@ impl Default for Config {
             exclude: vec![],
             excluded_files: RefCell::new(vec![]),
             excluded_files_raw: vec![],
+            ignore_matcher: RefCell::new(None),
+            no_ignore: false,
+            changed: false,
+            diff_base: None,
+            changed_files_cache: RefCell::new(None),
             varargs: vec![],
             test_timeout: Duration::from_secs(60),
             release: false,
@@ -140,24 +193,70 @@ impl Default for Config {
             frozen: false,
             target_dir: None,
             offline: false,
+            watch: false,
+            watch_debounce: Duration::from_millis(500),
         }
     }
 }
 
 impl<'a> From<&'a ArgMatches<'a>> for Config {
+    /// Builds the single config this invocation should run with. With
+    /// `--config --profile`, this is the first of the (possibly several)
+    /// selected profiles, resolved the same way `load_profiles` does; if
+    /// that fails (bad file, unknown profile, ...) the error is logged and
+    /// the CLI-args-only config is used as a fallback, consistent with the
+    /// pre-`--config` behavior. Callers that need *every* selected profile
+    /// (e.g. to aggregate several `--profile` runs) should call
+    /// `load_profiles` directly instead of going through `From`.
     fn from(args: &'a ArgMatches<'a>) -> Self {
+        match Self::load_profiles(args) {
+            Ok(mut confs) => confs.remove(0),
+            Err(e) => {
+                error!("{}", e);
+                Self::from_args(args)
+            }
+        }
+    }
+}
+
+impl Config {
+    /// Builds a config purely from CLI arguments, with no `--config` file
+    /// involved; this is the synthetic single "profile" used both when
+    /// `--config` is absent and as a fallback when it fails to resolve.
+    fn from_args(args: &ArgMatches) -> Self {
         info!("Creating config");
         let debug = args.is_present("debug");
         let verbose = args.is_present("verbose") || debug;
         let excluded_files = get_excluded(args);
         let excluded_files_raw = get_list(args, "exclude-files");
+        let run_types = get_run_types(args);
+        let is_nextest = run_types.contains(&RunType::Nextest);
+        // `cargo nextest run --profile`/`--partition` are flags to the
+        // nextest binary itself, not to the test executable, so they're
+        // kept as dedicated fields for the nextest invocation rather than
+        // folded into `varargs` (which is forwarded to the test exe after
+        // `--`). They're only meaningful when actually running nextest.
+        let nextest_profile = if is_nextest {
+            args.value_of("nextest-profile").map(String::from)
+        } else {
+            None
+        };
+        let nextest_partition = if is_nextest {
+            args.value_of("partition").map(String::from)
+        } else {
+            None
+        };
+        let varargs = get_list(args, "args");
 
         let args_config = Config {
             name: String::new(),
             manifest: get_manifest(args),
             config: None,
+            inherits: None,
             root: get_root(args),
-            run_types: get_run_types(args),
+            run_types,
+            nextest_profile,
+            nextest_partition,
             run_ignored: args.is_present("ignored"),
             ignore_tests: args.is_present("ignore-tests"),
             ignore_panics: args.is_present("ignore-panics"),
@@ -182,7 +281,12 @@ impl<'a> From<&'a ArgMatches<'a>> for Config {
             exclude: get_list(args, "exclude"),
             excluded_files: RefCell::new(excluded_files),
             excluded_files_raw,
-            varargs: get_list(args, "args"),
+            ignore_matcher: RefCell::new(None),
+            no_ignore: args.is_present("no-ignore"),
+            changed: args.is_present("changed"),
+            diff_base: args.value_of("diff-base").map(String::from),
+            changed_files_cache: RefCell::new(None),
+            varargs,
             test_timeout: get_timeout(args),
             release: args.is_present("release"),
             no_run: args.is_present("no-run"),
@@ -190,34 +294,11 @@ impl<'a> From<&'a ArgMatches<'a>> for Config {
             frozen: args.is_present("frozen"),
             target_dir: get_target_dir(args),
             offline: args.is_present("offline"),
+            watch: args.is_present("watch"),
+            watch_debounce: get_watch_debounce(args),
         };
 
-        if args.is_present("config") {
-            let mut path = PathBuf::from(args.value_of("config").unwrap());
-            if path.is_relative() {
-                path = env::current_dir()
-                    .unwrap()
-                    .join(path)
-                    .canonicalize()
-                    .unwrap();
-            }
-            let confs = Config::load_config_file(&path);
-            if confs.is_err() {
-                args_config
-            } else {
-                let mut confs = confs.unwrap();
-                for c in confs.iter_mut() {
-                    c.config = Some(path.clone());
-                }
-                if confs.is_empty() {
-                    args_config
-                } else {
-                    confs.remove(0)
-                }
-            }
-        } else {
-            args_config
-        }
+        args_config
     }
 }
 
@@ -226,15 +307,19 @@ impl Config {
         let mut f = File::open(file)?;
         let mut buffer = Vec::new();
         f.read_to_end(&mut buffer)?;
-        let mut map: HashMap<String, Self> = toml::from_slice(&buffer).map_err(|e| {
+        let map: HashMap<String, ConfigOverlay> = toml::from_slice(&buffer).map_err(|e| {
             error!("Invalid config file {}", e);
             Error::new(ErrorKind::InvalidData, format!("{}", e))
         })?;
 
+        let mut resolved = Self::resolve_inheritance(map)?;
+
         let mut result = Vec::new();
-        let mut keys = map.keys().into_iter().cloned().collect::<Vec<_>>();
-        for k in keys.drain(..) {
-            let mut conf = map.remove(&k).unwrap();
+        let mut keys = resolved.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        for k in keys {
+            let overlay = resolved.remove(&k).unwrap();
+            let mut conf = overlay.into_config();
             conf.name = k;
             result.push(conf);
         }
@@ -245,6 +330,105 @@ impl Config {
         }
     }
 
+    /// Selects and fully resolves the profiles requested for this
+    /// invocation. Without `--config`, CLI args always describe a single
+    /// synthetic profile. With `--config`, `--profile` may be given more
+    /// than once to select several named tables, all of which are
+    /// returned (aggregating their runs is left to the caller; `From`
+    /// keeps only the first); omitting it keeps the historical
+    /// behavior of running just the first table in the file.
+    pub fn load_profiles(args: &ArgMatches) -> std::io::Result<Vec<Config>> {
+        if !args.is_present("config") {
+            return Ok(vec![Config::from_args(args)]);
+        }
+
+        let mut path = PathBuf::from(args.value_of("config").unwrap());
+        if path.is_relative() {
+            path = env::current_dir().unwrap().join(path).canonicalize().unwrap();
+        }
+
+        let mut confs = Config::load_config_file(&path)?;
+        for c in confs.iter_mut() {
+            c.config = Some(path.clone());
+        }
+
+        let requested = get_list(args, "profile");
+        if requested.is_empty() {
+            Ok(vec![confs.remove(0)])
+        } else {
+            let selected: Vec<Config> = confs
+                .into_iter()
+                .filter(|c| requested.contains(&c.name))
+                .collect();
+            if selected.len() != requested.len() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("one or more requested profiles not found in {}", path.display()),
+                ));
+            }
+            Ok(selected)
+        }
+    }
+
+    /// Resolves `inherits` chains in a freshly parsed map of named
+    /// profile overlays, merging each child over its ancestors before
+    /// handing the fully-merged overlays back. Returns an error on an
+    /// unknown parent name or an inheritance cycle.
+    fn resolve_inheritance(
+        mut raw: HashMap<String, ConfigOverlay>,
+    ) -> std::io::Result<HashMap<String, ConfigOverlay>> {
+        let mut resolved: HashMap<String, ConfigOverlay> = HashMap::new();
+        let names = raw.keys().cloned().collect::<Vec<_>>();
+        for name in names {
+            Self::resolve_one(&name, &mut raw, &mut resolved, &mut Vec::new())?;
+        }
+        Ok(resolved)
+    }
+
+    fn resolve_one(
+        name: &str,
+        raw: &mut HashMap<String, ConfigOverlay>,
+        resolved: &mut HashMap<String, ConfigOverlay>,
+        stack: &mut Vec<String>,
+    ) -> std::io::Result<()> {
+        if resolved.contains_key(name) {
+            return Ok(());
+        }
+        if stack.iter().any(|s| s == name) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "profile inheritance cycle detected: {} -> {}",
+                    stack.join(" -> "),
+                    name
+                ),
+            ));
+        }
+        let mut overlay = raw.remove(name).ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "profile '{}' inherits from unknown profile '{}'",
+                    stack.last().map(String::as_str).unwrap_or(""),
+                    name
+                ),
+            )
+        })?;
+
+        if let Some(parent_name) = overlay.inherits.clone() {
+            stack.push(name.to_string());
+            Self::resolve_one(&parent_name, raw, resolved, stack)?;
+            stack.pop();
+            let parent = resolved
+                .get(&parent_name)
+                .expect("parent profile was just resolved");
+            overlay = overlay.merged_over(parent);
+        }
+
+        resolved.insert(name.to_string(), overlay);
+        Ok(())
+    }
+
     #[inline]
     pub fn is_coveralls(&self) -> bool {
         self.coveralls.is_some()
@@ -259,10 +443,59 @@ impl Config {
         }
         let project = self.strip_base_dir(path);
 
-        self.excluded_files
+        if self
+            .excluded_files
             .borrow()
             .iter()
             .any(|x| x.is_match(project.to_str().unwrap_or("")))
+        {
+            return true;
+        }
+
+        if self.ignore_matcher.borrow().is_none() {
+            let matcher = GitignoreMatcher::discover(&self.get_base_dir(), self.no_ignore);
+            *self.ignore_matcher.borrow_mut() = Some(matcher);
+        }
+
+        if self
+            .ignore_matcher
+            .borrow()
+            .as_ref()
+            .map(|matcher| matcher.is_excluded(&project))
+            .unwrap_or(false)
+        {
+            return true;
+        }
+
+        self.changed && !self.is_changed_file(&project)
+    }
+
+    /// Returns whether `project_path` (already relative to the base dir) is
+    /// part of the changed-file set for `diff_base`, lazily discovering and
+    /// caching that set on first use. If the changed-file set can't be
+    /// determined (e.g. the base dir isn't a git repository), caching an
+    /// empty set here would make every file look unchanged and so get
+    /// excluded, silently turning the run into a zero-coverage report
+    /// instead of the diff it was asked for — so this aborts the run
+    /// instead, consistent with this module's other unrecoverable,
+    /// CLI-context environment failures (see `get_base_dir`).
+    fn is_changed_file(&self, project_path: &Path) -> bool {
+        if self.changed_files_cache.borrow().is_none() {
+            let base_dir = self.get_base_dir();
+            let diff_base = self.diff_base.as_deref().unwrap_or("HEAD");
+            let files = changed_files(&base_dir, diff_base).unwrap_or_else(|e| {
+                panic!(
+                    "`changed` mode couldn't determine the changed-file set (would otherwise silently report zero coverage): {}",
+                    e
+                )
+            });
+            *self.changed_files_cache.borrow_mut() = Some(files);
+        }
+        self.changed_files_cache
+            .borrow()
+            .as_ref()
+            .map(|files| files.contains(project_path))
+            .unwrap_or(false)
     }
 
     ///
@@ -296,11 +529,200 @@ impl Config {
     }
 }
 
+/// Per-field overlay mirroring the mergeable subset of [`Config`], with
+/// every otherwise-infallible field wrapped in `Option` so a profile
+/// table that never mentions a field (`None`) can be told apart from one
+/// that explicitly sets it back to the value `Config::default()` would
+/// use anyway — the gap in the old "compare to `Config::default()`"
+/// merge, which could never let a child override a field *back to* its
+/// default. TOML profile tables deserialize into this instead of
+/// `Config` directly; `merged_over` then merges overlays along an
+/// `inherits` chain with plain `Option::or` semantics, and `into_config`
+/// applies the fully-merged overlay onto `Config::default()`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ConfigOverlay {
+    inherits: Option<String>,
+    manifest: Option<PathBuf>,
+    root: Option<String>,
+    run_ignored: Option<bool>,
+    ignore_tests: Option<bool>,
+    ignore_panics: Option<bool>,
+    force_clean: Option<bool>,
+    verbose: Option<bool>,
+    debug: Option<bool>,
+    count: Option<bool>,
+    line_coverage: Option<bool>,
+    branch_coverage: Option<bool>,
+    output_directory: Option<PathBuf>,
+    coveralls: Option<String>,
+    ci_tool: Option<CiService>,
+    report_uri: Option<String>,
+    forward_signals: Option<bool>,
+    all_features: Option<bool>,
+    no_default_features: Option<bool>,
+    all: Option<bool>,
+    test_timeout: Option<Duration>,
+    release: Option<bool>,
+    no_run: Option<bool>,
+    locked: Option<bool>,
+    frozen: Option<bool>,
+    target_dir: Option<PathBuf>,
+    offline: Option<bool>,
+    watch: Option<bool>,
+    watch_debounce: Option<Duration>,
+    run_types: Option<Vec<RunType>>,
+    nextest_profile: Option<String>,
+    nextest_partition: Option<String>,
+    packages: Vec<String>,
+    exclude: Vec<String>,
+    excluded_files_raw: Vec<String>,
+    no_ignore: Option<bool>,
+    changed: Option<bool>,
+    diff_base: Option<String>,
+    varargs: Vec<String>,
+    features: Vec<String>,
+    unstable_features: Vec<String>,
+    generate: Option<Vec<OutputFile>>,
+}
+
+impl ConfigOverlay {
+    /// Merges `self` (the child) over `parent`: an `Option` field left
+    /// unset (`None`) by the child falls back to the parent's value, and
+    /// list fields are merged via `merge_list`.
+    fn merged_over(mut self, parent: &ConfigOverlay) -> ConfigOverlay {
+        macro_rules! inherit {
+            ($field:ident) => {
+                if self.$field.is_none() {
+                    self.$field = parent.$field.clone();
+                }
+            };
+        }
+
+        inherit!(manifest);
+        inherit!(root);
+        inherit!(run_ignored);
+        inherit!(ignore_tests);
+        inherit!(ignore_panics);
+        inherit!(force_clean);
+        inherit!(verbose);
+        inherit!(debug);
+        inherit!(count);
+        inherit!(line_coverage);
+        inherit!(branch_coverage);
+        inherit!(output_directory);
+        inherit!(coveralls);
+        inherit!(ci_tool);
+        inherit!(report_uri);
+        inherit!(forward_signals);
+        inherit!(all_features);
+        inherit!(no_default_features);
+        inherit!(all);
+        inherit!(test_timeout);
+        inherit!(release);
+        inherit!(no_run);
+        inherit!(locked);
+        inherit!(frozen);
+        inherit!(target_dir);
+        inherit!(offline);
+        inherit!(watch);
+        inherit!(watch_debounce);
+        inherit!(run_types);
+        inherit!(nextest_profile);
+        inherit!(nextest_partition);
+        inherit!(no_ignore);
+        inherit!(changed);
+        inherit!(diff_base);
+        inherit!(generate);
+
+        self.packages = merge_list(&parent.packages, self.packages);
+        self.exclude = merge_list(&parent.exclude, self.exclude);
+        self.excluded_files_raw = merge_list(&parent.excluded_files_raw, self.excluded_files_raw);
+        self.varargs = merge_list(&parent.varargs, self.varargs);
+        self.features = merge_list(&parent.features, self.features);
+        self.unstable_features = merge_list(&parent.unstable_features, self.unstable_features);
+
+        self
+    }
+
+    /// Applies this fully-merged overlay onto `Config::default()`,
+    /// producing the concrete config a profile resolves to. `inherits` is
+    /// kept as the profile's own declared parent name (informational; it
+    /// plays no further role once the overlay chain has been merged).
+    fn into_config(self) -> Config {
+        let default = Config::default();
+        Config {
+            inherits: self.inherits,
+            manifest: self.manifest.unwrap_or(default.manifest),
+            root: self.root,
+            run_ignored: self.run_ignored.unwrap_or(default.run_ignored),
+            ignore_tests: self.ignore_tests.unwrap_or(default.ignore_tests),
+            ignore_panics: self.ignore_panics.unwrap_or(default.ignore_panics),
+            force_clean: self.force_clean.unwrap_or(default.force_clean),
+            verbose: self.verbose.unwrap_or(default.verbose),
+            debug: self.debug.unwrap_or(default.debug),
+            count: self.count.unwrap_or(default.count),
+            line_coverage: self.line_coverage.unwrap_or(default.line_coverage),
+            branch_coverage: self.branch_coverage.unwrap_or(default.branch_coverage),
+            output_directory: self.output_directory.unwrap_or(default.output_directory),
+            coveralls: self.coveralls,
+            ci_tool: self.ci_tool,
+            report_uri: self.report_uri,
+            forward_signals: self.forward_signals.unwrap_or(default.forward_signals),
+            all_features: self.all_features.unwrap_or(default.all_features),
+            no_default_features: self
+                .no_default_features
+                .unwrap_or(default.no_default_features),
+            all: self.all.unwrap_or(default.all),
+            test_timeout: self.test_timeout.unwrap_or(default.test_timeout),
+            release: self.release.unwrap_or(default.release),
+            no_run: self.no_run.unwrap_or(default.no_run),
+            locked: self.locked.unwrap_or(default.locked),
+            frozen: self.frozen.unwrap_or(default.frozen),
+            target_dir: self.target_dir,
+            offline: self.offline.unwrap_or(default.offline),
+            watch: self.watch.unwrap_or(default.watch),
+            watch_debounce: self.watch_debounce.unwrap_or(default.watch_debounce),
+            run_types: self.run_types.unwrap_or(default.run_types),
+            nextest_profile: self.nextest_profile,
+            nextest_partition: self.nextest_partition,
+            packages: self.packages,
+            exclude: self.exclude,
+            excluded_files_raw: self.excluded_files_raw,
+            no_ignore: self.no_ignore.unwrap_or(default.no_ignore),
+            changed: self.changed.unwrap_or(default.changed),
+            diff_base: self.diff_base,
+            varargs: self.varargs,
+            features: self.features,
+            unstable_features: self.unstable_features,
+            generate: self.generate.unwrap_or(default.generate),
+            ..default
+        }
+    }
+}
+
+/// Merges a child profile's list with its parent's. An empty child list
+/// inherits the parent's list unchanged; a child list whose entries are
+/// all prefixed with `+` has that prefix stripped and is appended to the
+/// parent's list; otherwise the child's list replaces the parent's
+/// entirely.
+fn merge_list(parent: &[String], child: Vec<String>) -> Vec<String> {
+    if child.is_empty() {
+        parent.to_vec()
+    } else if child.iter().all(|v| v.starts_with('+')) {
+        let mut merged = parent.to_vec();
+        merged.extend(child.into_iter().map(|v| v.trim_start_matches('+').to_string()));
+        merged
+    } else {
+        child
+    }
+}
+
 /// Gets the relative path from one directory to another, if it exists.
 /// Credit to brson from this commit from 2015
 /// https://github.com/rust-lang/rust/pull/23283/files
 ///
-fn path_relative_from(path: &Path, base: &Path) -> Option<PathBuf> {
+pub(crate) fn path_relative_from(path: &Path, base: &Path) -> Option<PathBuf> {
     use std::path::Component;
 
     if path.is_absolute() != base.is_absolute() {
@@ -345,7 +767,6 @@ fn path_relative_from(path: &Path, base: &Path) -> Option<PathBuf> {
 mod tests {
     use super::*;
     use clap::App;
-    use std::collections::HashMap;
 
     #[test]
     fn exclude_paths() {
@@ -386,6 +807,29 @@ mod tests {
         assert!(!conf.exclude_path(Path::new("lib.rs")));
     }
 
+    #[test]
+    fn tarpaulinignore_file_excludes_matching_paths() {
+        let dir = std::env::temp_dir().join(format!(
+            "tarpaulin-ignore-e2e-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(".tarpaulinignore"), "/target\n*.generated.rs\n").unwrap();
+
+        let matches = App::new("tarpaulin")
+            .args_from_usage("--root [DIR] 'Root directory'")
+            .get_matches_from_safe(vec!["tarpaulin", "--root", dir.to_str().unwrap()])
+            .unwrap();
+        let conf = Config::from(&matches);
+
+        assert!(conf.exclude_path(&dir.join("target/debug/build.rs")));
+        assert!(conf.exclude_path(&dir.join("foo.generated.rs")));
+        assert!(!conf.exclude_path(&dir.join("src/lib.rs")));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
     #[test]
     fn relative_path_test() {
         let path_a = Path::new("/this/should/form/a/rel/path/");
@@ -416,4 +860,146 @@ mod tests {
             "Wrong relative path"
         );
     }
+
+    fn write_profile_config(dir: &Path, contents: &str) -> PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join("tarpaulin.toml");
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn unknown_profile_falls_back_to_cli_config_instead_of_exiting() {
+        let dir = std::env::temp_dir().join(format!(
+            "tarpaulin-profile-fallback-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let config_path = write_profile_config(&dir, "[ci]\nrelease = true\n");
+
+        let matches = App::new("tarpaulin")
+            .args_from_usage(
+                "--config [FILE] 'Config file'
+                 --profile [NAME]... 'Profile name'",
+            )
+            .get_matches_from_safe(vec![
+                "tarpaulin",
+                "--config",
+                config_path.to_str().unwrap(),
+                "--profile",
+                "does-not-exist",
+            ])
+            .unwrap();
+
+        // Used to call `std::process::exit` here; now it falls back to the
+        // CLI-args config (the historical no-`--config` behavior) instead of
+        // tearing down the whole process from inside `From`.
+        let conf = Config::from(&matches);
+        assert_eq!(conf.name, "");
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn load_profiles_errors_loudly_on_unknown_profile_name() {
+        let dir = std::env::temp_dir().join(format!(
+            "tarpaulin-profile-unknown-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let config_path = write_profile_config(&dir, "[ci]\nrelease = true\n");
+
+        let matches = App::new("tarpaulin")
+            .args_from_usage(
+                "--config [FILE] 'Config file'
+                 --profile [NAME]... 'Profile name'",
+            )
+            .get_matches_from_safe(vec![
+                "tarpaulin",
+                "--config",
+                config_path.to_str().unwrap(),
+                "--profile",
+                "does-not-exist",
+            ])
+            .unwrap();
+
+        assert!(Config::load_profiles(&matches).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn load_profiles_aggregates_several_requested_profiles() {
+        let dir = std::env::temp_dir().join(format!(
+            "tarpaulin-profile-aggregate-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let config_path =
+            write_profile_config(&dir, "[ci]\nrelease = true\n\n[local]\nrelease = false\n");
+
+        let matches = App::new("tarpaulin")
+            .args_from_usage(
+                "--config [FILE] 'Config file'
+                 --profile [NAME]... 'Profile name'",
+            )
+            .get_matches_from_safe(vec![
+                "tarpaulin",
+                "--config",
+                config_path.to_str().unwrap(),
+                "--profile",
+                "ci",
+                "--profile",
+                "local",
+            ])
+            .unwrap();
+
+        let confs = Config::load_profiles(&matches).unwrap();
+        let names: HashSet<_> = confs.iter().map(|c| c.name.clone()).collect();
+        assert_eq!(names, ["ci", "local"].iter().map(|s| s.to_string()).collect());
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn profile_inheritance_can_override_a_bool_back_to_the_default() {
+        let dir = std::env::temp_dir().join(format!(
+            "tarpaulin-profile-override-default-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        let config_path = write_profile_config(
+            &dir,
+            "[base]\nrelease = true\n\n[child]\ninherits = \"base\"\nrelease = false\n",
+        );
+
+        let confs = Config::load_config_file(&config_path).unwrap();
+        let child = confs.iter().find(|c| c.name == "child").unwrap();
+        // Before the `ConfigOverlay` rework, comparing against
+        // `Config::default()` made this indistinguishable from "unset" and
+        // the child could never flip `release` back off.
+        assert!(!child.release);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    #[should_panic(expected = "changed` mode couldn't determine the changed-file set")]
+    fn changed_mode_aborts_rather_than_silently_reporting_zero_coverage() {
+        let dir = std::env::temp_dir().join(format!(
+            "tarpaulin-changed-not-a-repo-{}-{}",
+            std::process::id(),
+            line!()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let matches = App::new("tarpaulin")
+            .args_from_usage("--root [DIR] 'Root directory'")
+            .get_matches_from_safe(vec!["tarpaulin", "--root", dir.to_str().unwrap()])
+            .unwrap();
+        let mut conf = Config::from(&matches);
+        conf.changed = true;
+
+        conf.exclude_path(Path::new("src/lib.rs"));
+    }
 }
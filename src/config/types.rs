@@ -0,0 +1,26 @@
+//! Supporting enum types used by [`Config`](super::Config).
+
+use serde::{Deserialize, Serialize};
+
+/// The different kinds of test run tarpaulin can instrument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum RunType {
+    /// Run the project's tests via `cargo test`.
+    Tests,
+    /// Selects `cargo nextest run` as the test runner. Its nextest-only
+    /// options are kept in `Config::nextest_profile` and
+    /// `Config::nextest_partition` rather than in `varargs`. The config
+    /// only records this selection: no code in this crate invokes
+    /// `cargo nextest` or dispatches on this variant.
+    Nextest,
+}
+
+/// Output report formats tarpaulin can generate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum OutputFile {
+    Html,
+    Xml,
+    Json,
+    Stdout,
+    Lcov,
+}
@@ -0,0 +1,167 @@
+//! Watch mode: after the initial coverage run, keep the process alive,
+//! watch the project tree for source changes and rerun coverage whenever
+//! a relevant file changes, printing the coverage delta between runs.
+
+use super::Config;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc::{channel, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// A minimal summary of a single coverage run, just enough to print a
+/// concise delta between watch-mode reruns. The real per-line report is
+/// produced elsewhere; `watch` only needs the totals `run` hands back.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoverageSummary {
+    pub covered: usize,
+    pub coverable: usize,
+}
+
+impl CoverageSummary {
+    /// Coverage percentage, or `0.0` if nothing was coverable.
+    pub fn percent(&self) -> f64 {
+        if self.coverable == 0 {
+            0.0
+        } else {
+            100.0 * (self.covered as f64) / (self.coverable as f64)
+        }
+    }
+}
+
+/// Watches `config`'s project tree and calls `run` once up front, then
+/// again every time a relevant `.rs` or `Cargo.toml` file changes, until
+/// `run` returns `None` (asking to stop) or the filesystem watcher itself
+/// fails. Kept-alive compiled artifacts in `config.target_dir` mean each
+/// rerun only needs to rebuild what actually changed.
+///
+/// Events are filtered through `config.exclude_path` before they ever
+/// reach the debouncer, so excluded churn (paths matched by exclude
+/// patterns or ignore files) doesn't trigger reruns, and rapid bursts of events from a single save
+/// are coalesced by [`Debouncer`] into one rerun.
+pub fn watch<F>(config: &Config, mut run: F) -> notify::Result<()>
+where
+    F: FnMut() -> Option<CoverageSummary>,
+{
+    let base_dir = config.get_base_dir();
+    let (tx, rx) = channel();
+    let mut watcher: RecommendedWatcher =
+        notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            let _ = tx.send(res);
+        })?;
+    watcher.watch(&base_dir, RecursiveMode::Recursive)?;
+
+    let mut previous = match run() {
+        Some(summary) => summary,
+        None => return Ok(()),
+    };
+    println!("coverage: {:.2}%", previous.percent());
+
+    let mut debouncer = Debouncer::new(config.watch_debounce);
+    loop {
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(Ok(event)) => {
+                let relevant = event.paths.iter().any(|path| is_relevant(config, path));
+                if relevant {
+                    debouncer.event();
+                }
+            }
+            Ok(Err(e)) => return Err(e),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        if debouncer.should_rerun() {
+            let current = match run() {
+                Some(summary) => summary,
+                None => return Ok(()),
+            };
+            print_delta(previous, current);
+            previous = current;
+        }
+    }
+}
+
+/// Whether a changed path is one watch mode should react to: a `.rs`
+/// source file or a `Cargo.toml` manifest that isn't otherwise excluded
+/// (exclude patterns, ignore files, per `config.exclude_path`).
+fn is_relevant(config: &Config, path: &Path) -> bool {
+    let is_source = path.file_name().and_then(|name| name.to_str()) == Some("Cargo.toml")
+        || path.extension().and_then(|ext| ext.to_str()) == Some("rs");
+    is_source && !config.exclude_path(&config.strip_base_dir(path))
+}
+
+fn print_delta(previous: CoverageSummary, current: CoverageSummary) {
+    let delta = current.percent() - previous.percent();
+    let sign = if delta >= 0.0 { "+" } else { "" };
+    println!(
+        "coverage: {:.2}% ({}{:.2}%)",
+        current.percent(),
+        sign,
+        delta
+    );
+}
+
+/// Coalesces rapid filesystem events during watch mode into a single
+/// coverage rerun.
+
+/// Coalesces rapid filesystem events into a single rerun signal: repeated
+/// calls to `event` within `window` of each other count as one burst, and
+/// `should_rerun` only returns `true` once that burst has gone quiet.
+pub struct Debouncer {
+    window: Duration,
+    last_event: Option<Instant>,
+}
+
+impl Debouncer {
+    pub fn new(window: Duration) -> Self {
+        Debouncer {
+            window,
+            last_event: None,
+        }
+    }
+
+    /// Records a file system event, restarting the quiet-period countdown.
+    pub fn event(&mut self) {
+        self.last_event = Some(Instant::now());
+    }
+
+    /// Returns whether enough quiet time has passed since the last event
+    /// to trigger a rerun. Returns `false` (without resetting) if no event
+    /// has been recorded yet or the quiet period hasn't elapsed.
+    pub fn should_rerun(&mut self) -> bool {
+        match self.last_event {
+            Some(last) if last.elapsed() >= self.window => {
+                self.last_event = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_rerun_before_quiet_period_elapses() {
+        let mut debouncer = Debouncer::new(Duration::from_secs(60));
+        debouncer.event();
+        assert!(!debouncer.should_rerun());
+    }
+
+    #[test]
+    fn no_rerun_without_any_event() {
+        let mut debouncer = Debouncer::new(Duration::from_millis(1));
+        assert!(!debouncer.should_rerun());
+    }
+
+    #[test]
+    fn rerun_once_quiet_period_elapses() {
+        let mut debouncer = Debouncer::new(Duration::from_millis(1));
+        debouncer.event();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(debouncer.should_rerun());
+        assert!(!debouncer.should_rerun());
+    }
+}